@@ -7,6 +7,52 @@ use crate::scanner::Scanner;
 /// Based on RFC 3986.
 pub struct UrlScanner {
     pub trigger: char,
+
+    /// If set, only schemes in this list are linkified (case-insensitive). Schemes not in
+    /// the list cause `scan` to reject the match entirely. Defaults to a common set of
+    /// schemes in [`UrlScanner::default`]; set to `None` to accept any syntactically valid
+    /// scheme.
+    pub schemes: Option<Vec<String>>,
+
+    /// If `true`, `%` must always be followed by two hex digits; a lone or truncated escape
+    /// ends the URL just before the `%`. If `false` (the default), `%` is treated as an
+    /// ordinary character, matching the historical, lenient behavior.
+    pub validate_percent_encoding: bool,
+
+    /// Extra characters that, like the built-in `? ! . , : ;`, may appear inside a URL but
+    /// never terminate it (a trailing one is trimmed). Layered on top of the RFC 3987
+    /// defaults in `find_end`. Empty by default.
+    pub deny_end_chars: Vec<char>,
+
+    /// Extra characters that, like the built-in control characters, space, `" < > ` `, end a
+    /// URL immediately wherever they're found. Layered on top of the RFC 3987 defaults in
+    /// `find_end`. Empty by default.
+    pub separator_chars: Vec<char>,
+
+    /// If `true`, a match enclosed in a matching pair of `< >`, `( )`, `[ ]`, `'`, `"`, or `` ` ``
+    /// (per RFC 3987's discussion of angle-bracketed URIs like `<http://go.here/to this
+    /// place>`) is terminated at the closing delimiter instead of the normal `find_end` rules,
+    /// allowing characters such as spaces that would otherwise end the URL early. The
+    /// delimiters themselves are excluded from the returned range. `false` by default.
+    pub delimited: bool,
+}
+
+impl Default for UrlScanner {
+    fn default() -> Self {
+        Self {
+            trigger: ':',
+            schemes: Some(
+                ["http", "https", "ftp", "ftps", "mailto", "file", "git", "ssh", "news"]
+                    .iter()
+                    .map(|s| s.to_string())
+                    .collect(),
+            ),
+            validate_percent_encoding: false,
+            deny_end_chars: Vec::new(),
+            separator_chars: Vec::new(),
+            delimited: false,
+        }
+    }
 }
 
 impl Scanner for UrlScanner {
@@ -19,7 +65,20 @@ impl Scanner for UrlScanner {
         // Need at least one character for scheme, and one after '//'
         if after_slash_slash < s.len() && s[trigger_index..].starts_with(proto) {
             if let Some(start) = self.find_start(&s[0..trigger_index]) {
-                if let Some(end) = self.find_end(&s[after_slash_slash..]) {
+                if self.trigger != '/' && !self.scheme_allowed(&s[start..trigger_index]) {
+                    return None;
+                }
+                let stop_at = self
+                    .delimited
+                    .then(|| closing_delimiter(s[..start].chars().next_back()))
+                    .flatten();
+                // If the opening delimiter's matching close is never actually reached, fall
+                // back to the normal (non-relaxed) rules rather than swallowing the rest of
+                // the input.
+                let end = stop_at
+                    .and_then(|_| self.find_end(&s[after_slash_slash..], stop_at))
+                    .or_else(|| self.find_end(&s[after_slash_slash..], None));
+                if let Some(end) = end {
                     let range = Range {
                         start,
                         end: after_slash_slash + end,
@@ -32,7 +91,133 @@ impl Scanner for UrlScanner {
     }
 }
 
+// Map an opening delimiter immediately preceding a URL match to its matching close, per the
+// set used by `UrlScanner::delimited`.
+fn closing_delimiter(open: Option<char>) -> Option<char> {
+    match open? {
+        '<' => Some('>'),
+        '(' => Some(')'),
+        '[' => Some(']'),
+        c @ ('\'' | '"' | '`') => Some(c),
+        _ => None,
+    }
+}
+
+/// The RFC 3986 components of a URL matched by [`UrlScanner`], as byte ranges into the
+/// original input.
+///
+/// Obtained via [`UrlScanner::parts`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UrlParts {
+    pub scheme: Range<usize>,
+    pub userinfo: Option<Range<usize>>,
+    pub host: Range<usize>,
+    pub port: Option<u16>,
+    pub path: Range<usize>,
+    pub query: Option<Range<usize>>,
+    pub fragment: Option<Range<usize>>,
+}
+
 impl UrlScanner {
+    /// Split a URL matched by [`Scanner::scan`] into its RFC 3986 components.
+    ///
+    /// `range` must be a match previously returned by `scan` for `s`; `s[range]` is expected to
+    /// contain a `scheme://authority` prefix, i.e. only `http`/`https`-like URLs are
+    /// supported, not e.g. `mailto:` URLs or protocol-relative (`//example.org`) matches from
+    /// a `trigger: '/'` scanner, both of which return `None`.
+    pub fn parts(&self, s: &str, range: Range<usize>) -> Option<UrlParts> {
+        let url = &s[range.clone()];
+        let scheme_end = url.find(':')?;
+        let scheme_str = &url[..scheme_end];
+        if scheme_str.is_empty()
+            || !scheme_str.starts_with(|c: char| c.is_ascii_alphabetic())
+            || !scheme_str
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+        {
+            return None;
+        }
+        let scheme = range.start..range.start + scheme_end;
+
+        let rest = &url[scheme_end..];
+        if !rest.starts_with("://") {
+            return None;
+        }
+        let authority_start = range.start + scheme_end + 3;
+        let authority_rel = &url[scheme_end + 3..];
+
+        let path_start_rel = authority_rel
+            .find(['/', '?', '#'])
+            .unwrap_or(authority_rel.len());
+        let authority = &authority_rel[..path_start_rel];
+
+        // The last `@` wins, matching `find_ip_literal_host_end`'s rule for where the host
+        // starts; an unencoded `@` has no business appearing earlier in a userinfo, but if one
+        // slips through both should agree on the split.
+        let (userinfo, host_and_port) = match authority.rfind('@') {
+            Some(at) => (
+                Some(authority_start..authority_start + at),
+                &authority[at + 1..],
+            ),
+            None => (None, authority),
+        };
+        let host_and_port_start = authority_start + (authority.len() - host_and_port.len());
+
+        let (host, port) = if host_and_port.starts_with('[') {
+            // IP-literal host, the port (if any) follows the closing bracket.
+            match host_and_port.find(']') {
+                Some(close) => {
+                    let host = host_and_port_start..host_and_port_start + close + 1;
+                    // An invalid or out-of-range port shouldn't fail the whole decomposition;
+                    // degrade to "no port", same as if there were none.
+                    let port = parse_port(&host_and_port[close + 1..]).unwrap_or(None);
+                    (host, port)
+                }
+                None => (host_and_port_start..host_and_port_start + host_and_port.len(), None),
+            }
+        } else {
+            match host_and_port.find(':') {
+                Some(colon) => {
+                    let host = host_and_port_start..host_and_port_start + colon;
+                    let port = parse_port(&host_and_port[colon..]).unwrap_or(None);
+                    (host, port)
+                }
+                None => (
+                    host_and_port_start..host_and_port_start + host_and_port.len(),
+                    None,
+                ),
+            }
+        };
+
+        let rest_start = range.start + scheme_end + 3 + path_start_rel;
+        let rest_str = &url[scheme_end + 3 + path_start_rel..];
+
+        let query_start_rel = rest_str.find('?');
+        let fragment_start_rel = rest_str.find('#');
+
+        let path_end_rel = query_start_rel
+            .or(fragment_start_rel)
+            .unwrap_or(rest_str.len());
+        let path = rest_start..rest_start + path_end_rel;
+
+        let query = query_start_rel.map(|q| {
+            let end = fragment_start_rel.unwrap_or(rest_str.len());
+            rest_start + q..rest_start + end
+        });
+
+        let fragment = fragment_start_rel.map(|f| rest_start + f..rest_start + rest_str.len());
+
+        Some(UrlParts {
+            scheme,
+            userinfo,
+            host,
+            port,
+            path,
+            query,
+            fragment,
+        })
+    }
+
     // See "scheme" in RFC 3986
     fn find_start(&self, s: &str) -> Option<usize> {
         // Match protocol relative URLs (`//example.org`)
@@ -68,17 +253,44 @@ impl UrlScanner {
         first
     }
 
-    fn find_end(&self, s: &str) -> Option<usize> {
+    // Check the scheme found by `find_start` against the configured allowlist, if any.
+    fn scheme_allowed(&self, scheme: &str) -> bool {
+        match &self.schemes {
+            Some(schemes) => schemes.iter().any(|s| s.eq_ignore_ascii_case(scheme)),
+            None => true,
+        }
+    }
+
+    // `stop_at`, set when `UrlScanner::delimited` found an enclosing delimiter, is the matching
+    // close character; the normal stop rules below are relaxed for every such character except
+    // `stop_at` itself, which still terminates the URL (and is excluded from the result).
+    fn find_end(&self, s: &str, stop_at: Option<char>) -> Option<usize> {
         let mut round = 0;
         let mut square = 0;
         let mut curly = 0;
         let mut single_quote = false;
 
+        // If the authority starts with an IP-literal (`[...]`, e.g. an IPv6 address), it's
+        // validated and consumed up front rather than via the generic bracket counter below,
+        // so that brackets elsewhere in the URL aren't mistaken for IPv6 grouping and a
+        // malformed bracket run fails the whole host instead of silently truncating it.
+        let host_end = match self.find_ip_literal_host_end(s) {
+            Ok(host_end) => host_end,
+            Err(()) => return None,
+        };
+
         let mut previous_can_be_last = true;
-        let mut end = None;
+        let mut end = host_end;
+        let mut found_delimiter = false;
 
-        for (i, c) in s.char_indices() {
+        for (i, c) in s[host_end.unwrap_or(0)..].char_indices() {
+            let i = i + host_end.unwrap_or(0);
             let can_be_last = match c {
+                c if self.separator_chars.contains(&c) && stop_at != Some(c) => {
+                    // Caller-supplied hard stop, checked before the defaults below so that it
+                    // also applies to characters that have a built-in rule (such as `)` or `/`)
+                    break;
+                }
                 '\u{00}'..='\u{1F}' | ' ' | '\"' | '<' | '>' | '`' | '\u{7F}'..='\u{9F}' => {
                     // These can never be part of an URL, so stop now. See RFC 3986 and RFC 3987.
                     // Some characters are not in the above list, even they are not in "unreserved"
@@ -86,7 +298,18 @@ impl UrlScanner {
                     //   '\\', '^', '{', '|', '}'
                     // The reason for this is that other link detectors also allow them. Also see
                     // below, we require the braces to be balanced.
-                    break;
+                    //
+                    // Exception: inside a delimited match (`UrlScanner::delimited`) these are
+                    // relaxed to ordinary characters, since the enclosing delimiter is the
+                    // terminator instead.
+                    if stop_at == Some(c) {
+                        found_delimiter = true;
+                        break;
+                    }
+                    if stop_at.is_none() {
+                        break;
+                    }
+                    true
                 }
                 '?' | '!' | '.' | ',' | ':' | ';' => {
                     // These may be part of an URL but not at the end
@@ -105,20 +328,26 @@ impl UrlScanner {
                     round -= 1;
                     if round < 0 {
                         // More closing than opening brackets, stop now
+                        if stop_at == Some(c) {
+                            found_delimiter = true;
+                        }
                         break;
                     }
                     true
                 }
                 '[' => {
-                    // Allowed in IPv6 address host
+                    // Balanced brackets elsewhere in the URL (e.g. in the path), not the
+                    // authority's IP-literal host, which is handled separately above.
                     square += 1;
                     false
                 }
                 ']' => {
-                    // Allowed in IPv6 address host
                     square -= 1;
                     if square < 0 {
                         // More closing than opening brackets, stop now
+                        if stop_at == Some(c) {
+                            found_delimiter = true;
+                        }
                         break;
                     }
                     true
@@ -136,18 +365,521 @@ impl UrlScanner {
                     true
                 }
                 '\'' => {
+                    if stop_at == Some(c) {
+                        found_delimiter = true;
+                        break;
+                    }
                     single_quote = !single_quote;
                     // A single quote can only be the end of an URL if there's an even number
                     !single_quote
                 }
+                '%' if self.validate_percent_encoding => {
+                    let valid = matches!(s.as_bytes().get(i + 1), Some(b) if b.is_ascii_hexdigit())
+                        && matches!(s.as_bytes().get(i + 2), Some(b) if b.is_ascii_hexdigit());
+                    if !valid {
+                        // Stray or truncated escape, stop just before the '%'
+                        break;
+                    }
+                    false
+                }
+                c if self.deny_end_chars.contains(&c) => {
+                    // Caller-supplied "may appear but not at the end", layered on top of the
+                    // defaults above
+                    false
+                }
                 _ => true,
             };
+            // A caller-supplied deny-end character never ends the URL, even one that has a
+            // built-in rule above (such as `)` or `/`).
+            let can_be_last = can_be_last && !self.deny_end_chars.contains(&c);
             if can_be_last {
                 end = Some(i + c.len_utf8());
             }
             previous_can_be_last = can_be_last;
         }
 
+        // A delimited match must actually reach its closing delimiter; if the input ran out
+        // first, the relaxed rules above shouldn't have applied at all.
+        if stop_at.is_some() && !found_delimiter {
+            return None;
+        }
+
         end
     }
+
+    // `s` is the text right after "://". If the authority (optionally preceded by a
+    // `userinfo@`) starts with `[`, validate it as an RFC 3986 IP-literal: only hex digits,
+    // `:`, `.`, and a `%zone` suffix are allowed inside the brackets, terminated by `]` before
+    // any `/`, `?`, or `#`. Returns `Ok(Some(end))` with the byte offset right after the `]` if
+    // a valid IP-literal was found, `Ok(None)` if the host doesn't start with `[`, and `Err(())`
+    // if it does but is malformed (in which case the whole URL should be rejected).
+    fn find_ip_literal_host_end(&self, s: &str) -> Result<Option<usize>, ()> {
+        let authority_end = s.find(['/', '?', '#']).unwrap_or(s.len());
+        let authority = &s[..authority_end];
+        let host_start = authority.rfind('@').map_or(0, |at| at + 1);
+        let host = &authority[host_start..];
+
+        if !host.starts_with('[') {
+            return Ok(None);
+        }
+
+        let close = host.find(']').ok_or(())?;
+        let inner = &host[1..close];
+        let (address, zone) = match inner.find('%') {
+            Some(p) => (&inner[..p], Some(&inner[p + 1..])),
+            None => (inner, None),
+        };
+        if !address.chars().all(|c| c.is_ascii_hexdigit() || c == ':' || c == '.') {
+            return Err(());
+        }
+        if let Some(zone) = zone {
+            if zone.is_empty() || !zone.chars().all(|c| c.is_ascii_alphanumeric() || "._~-".contains(c))
+            {
+                return Err(());
+            }
+        }
+
+        Ok(Some(host_start + close + 1))
+    }
+}
+
+// Parse an optional `:port` suffix as used by `UrlScanner::parts`. An empty string means no
+// port was present; a non-numeric or out-of-range port is treated as a parse failure.
+fn parse_port(s: &str) -> Option<Option<u16>> {
+    match s.strip_prefix(':') {
+        None if s.is_empty() => Some(None),
+        None => None,
+        Some("") => Some(None),
+        Some(digits) => digits.parse::<u16>().ok().map(Some),
+    }
+}
+
+/// An incremental, bidirectional variant of [`UrlScanner`]'s scan for callers that don't have
+/// the whole line as a contiguous string up front, such as a terminal emulator growing a
+/// candidate URL outward from a mouse or cursor position known to be just past the `://` (for
+/// example, once a `://` has been spotted under or before the cursor while scanning outward).
+///
+/// Feed characters outward with [`advance_left`](Self::advance_left) and
+/// [`advance_right`](Self::advance_right) (closest to the anchor first, one at a time, in
+/// either order), stopping each side as soon as it returns `false`. Call
+/// [`finish`](Self::finish) to get the detected range, if any.
+///
+/// This holds the characters fed so far and, rather than re-deriving the acceptance rules,
+/// defers to the owning [`UrlScanner`]'s own `find_start`/`scheme_allowed`/`find_end` at
+/// [`finish`](Self::finish) time, so it automatically honors `schemes`, IP-literal host
+/// validation, `validate_percent_encoding`, `deny_end_chars`/`separator_chars` and `delimited`
+/// exactly like a batch `scan` would. `advance_left`/`advance_right` only give a cheap,
+/// best-effort "keep feeding me" signal in the meantime; a `true` from either isn't a guarantee
+/// that every character fed so far will end up in the final range.
+pub struct StreamingScanner<'a> {
+    scanner: &'a UrlScanner,
+    // Byte offset of the first character right after "://" (or "//" for a `/`-triggered scan).
+    after_protocol: usize,
+    stop_at: Option<char>,
+    // Characters fed to `advance_left`, closest-to-anchor first (i.e. reverse reading order).
+    left_chars: Vec<char>,
+    left_done: bool,
+    // Characters fed to `advance_right`, in reading order.
+    right_buf: String,
+    right_done: bool,
+}
+
+impl<'a> StreamingScanner<'a> {
+    /// Start growing a candidate URL outward. `scanner` is the configuration to validate
+    /// against; `after_protocol` is the byte offset of the first character right after the
+    /// `://`/`//` (i.e. where `UrlScanner::find_end` would start scanning); `advance_left` walks
+    /// backwards from just before the protocol and `advance_right` walks forwards from
+    /// `after_protocol`. `stop_at` is the closing delimiter to relax the end rules for, exactly
+    /// as computed for `UrlScanner::delimited` in `scan`.
+    pub fn new(scanner: &'a UrlScanner, after_protocol: usize, stop_at: Option<char>) -> Self {
+        Self {
+            scanner,
+            after_protocol,
+            stop_at,
+            left_chars: Vec::new(),
+            left_done: false,
+            right_buf: String::new(),
+            right_done: false,
+        }
+    }
+
+    /// Feed the next character to the left of the seed (closest to the seed first). Returns
+    /// whether it extends a valid scheme; once this returns `false`, stop feeding this side
+    /// and call `finish`.
+    pub fn advance_left(&mut self, c: char) -> bool {
+        if self.left_done {
+            return false;
+        }
+        if !matches!(c, 'a'..='z' | 'A'..='Z' | '0'..='9' | '+' | '-' | '.') {
+            self.left_done = true;
+            return false;
+        }
+        self.left_chars.push(c);
+        true
+    }
+
+    /// Feed the next character to the right of the seed (closest to the seed first). Returns
+    /// whether it extends the candidate URL; once this returns `false`, stop feeding this side
+    /// and call `finish`.
+    pub fn advance_right(&mut self, c: char) -> bool {
+        if self.right_done {
+            return false;
+        }
+        // A `)` or `]` that closes a bracket opened inside the URL isn't the closing delimiter,
+        // as `find_end` balances brackets before it checks for the delimiter.
+        let nested = match c {
+            ')' => self.right_buf.matches('(').count() > self.right_buf.matches(')').count(),
+            ']' => self.right_buf.matches('[').count() > self.right_buf.matches(']').count(),
+            _ => false,
+        };
+        if self.stop_at == Some(c) && !nested {
+            // Recorded (even though this char itself will never end up in the final range) so
+            // that `finish`'s call to `find_end` can confirm the closing delimiter was actually
+            // reached, same as it would scanning the whole remaining text in one go.
+            self.right_buf.push(c);
+            self.right_done = true;
+            return false;
+        }
+        if self.is_hard_stop(c) {
+            self.right_done = true;
+            return false;
+        }
+        self.right_buf.push(c);
+        true
+    }
+
+    // Characters that can never be part of a URL, regardless of position; mirrors the
+    // unconditional break arm in `UrlScanner::find_end`. The built-in set (but not
+    // `separator_chars`, which stay a hard stop) is relaxed when `stop_at` is set, exactly as
+    // `find_end` relaxes it inside a `UrlScanner::delimited` match.
+    fn is_hard_stop(&self, c: char) -> bool {
+        let builtin = matches!(c, '\u{00}'..='\u{1F}' | ' ' | '\"' | '<' | '>' | '`' | '\u{7F}'..='\u{9F}');
+        (builtin && self.stop_at.is_none()) || self.scanner.separator_chars.contains(&c)
+    }
+
+    /// Finalize the candidate grown so far, yielding its byte range in the original text, or
+    /// `None` if no valid URL was found (e.g. the scheme was rejected, or nothing to the right
+    /// could legally end a URL). This re-validates everything fed so far against the owning
+    /// `UrlScanner`, so the result may be shorter than what `advance_left`/`advance_right`
+    /// tentatively accepted.
+    pub fn finish(&self) -> Option<Range<usize>> {
+        let scheme: String = self.left_chars.iter().rev().collect();
+        // As in `scan`, the match starts where `find_start` puts it, which may skip leading
+        // characters such as `+`, `-` or `.`.
+        let start = self.scanner.find_start(&scheme)?;
+        let scheme = &scheme[start..];
+        if self.scanner.trigger != '/' && !self.scanner.scheme_allowed(scheme) {
+            return None;
+        }
+        // As in `scan`, fall back to the normal rules if the closing delimiter was never reached.
+        let right_len = self
+            .stop_at
+            .and_then(|_| self.scanner.find_end(&self.right_buf, self.stop_at))
+            .or_else(|| self.scanner.find_end(&self.right_buf, None))?;
+        let protocol_len = if self.scanner.trigger == '/' { 2 } else { 3 };
+        Some(
+            self.after_protocol - protocol_len - scheme.len()
+                ..self.after_protocol + right_len,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allows_default_schemes() {
+        let scanner = UrlScanner::default();
+        let s = "see http://example.com end";
+        assert_eq!(scanner.scan(s, s.find(':').unwrap()), Some(4..22));
+    }
+
+    #[test]
+    fn rejects_scheme_outside_allowlist() {
+        let scanner = UrlScanner {
+            schemes: Some(vec!["http".to_string(), "https".to_string()]),
+            ..Default::default()
+        };
+        let s = "javascript://alert(1)";
+        assert_eq!(scanner.scan(s, s.find(':').unwrap()), None);
+    }
+
+    #[test]
+    fn allowlist_is_case_insensitive() {
+        let scanner = UrlScanner {
+            schemes: Some(vec!["http".to_string()]),
+            ..Default::default()
+        };
+        let s = "HTTP://example.com";
+        assert_eq!(scanner.scan(s, s.find(':').unwrap()), Some(0..18));
+    }
+
+    #[test]
+    fn no_allowlist_accepts_any_syntactically_valid_scheme() {
+        let scanner = UrlScanner {
+            schemes: None,
+            ..Default::default()
+        };
+        let s = "coap://example.com";
+        assert_eq!(scanner.scan(s, s.find(':').unwrap()), Some(0..18));
+    }
+
+    #[test]
+    fn parts_decomposes_a_full_url() {
+        let scanner = UrlScanner::default();
+        let s = "see https://user@example.com:8443/path?q=1#frag end";
+        let range = scanner.scan(s, s.find(':').unwrap()).unwrap();
+        let parts = scanner.parts(s, range).unwrap();
+        assert_eq!(&s[parts.scheme], "https");
+        assert_eq!(parts.userinfo.map(|r| &s[r]), Some("user"));
+        assert_eq!(&s[parts.host], "example.com");
+        assert_eq!(parts.port, Some(8443));
+        assert_eq!(&s[parts.path], "/path");
+        assert_eq!(parts.query.map(|r| &s[r]), Some("?q=1"));
+        assert_eq!(parts.fragment.map(|r| &s[r]), Some("#frag"));
+    }
+
+    #[test]
+    fn parts_rejects_a_protocol_relative_match() {
+        // A `trigger: '/'` scanner can match a protocol-relative URL with no `scheme://`
+        // prefix; `parts` must not mistake an embedded colon further in for the scheme
+        // separator.
+        let scanner = UrlScanner {
+            trigger: '/',
+            ..Default::default()
+        };
+        let s = "//example.com/redirect?url=http://evil.com end";
+        let range = scanner.scan(s, 0).unwrap();
+        assert_eq!(scanner.parts(s, range), None);
+    }
+
+    #[test]
+    fn parts_degrades_an_invalid_port_to_none_instead_of_failing() {
+        // An out-of-range (or otherwise unparseable) port shouldn't discard the rest of an
+        // otherwise valid decomposition; `scan` doesn't validate ports, so `parts` can still be
+        // called on a match like this.
+        let scanner = UrlScanner::default();
+        let s = "see http://example.com:99999/path end";
+        let range = scanner.scan(s, s.find(':').unwrap()).unwrap();
+        let parts = scanner.parts(s, range).unwrap();
+        assert_eq!(&s[parts.host], "example.com");
+        assert_eq!(parts.port, None);
+        assert_eq!(&s[parts.path], "/path");
+    }
+
+    #[test]
+    fn parts_splits_userinfo_on_the_last_at_sign() {
+        // `find_ip_literal_host_end` already treats the last `@` as the userinfo/host
+        // boundary; `parts` must agree, or the two disagree on where the host starts whenever
+        // userinfo contains an unencoded `@`.
+        let scanner = UrlScanner::default();
+        let s = "see http://a@b@example.com/path end";
+        let range = scanner.scan(s, s.find(':').unwrap()).unwrap();
+        let parts = scanner.parts(s, range).unwrap();
+        assert_eq!(parts.userinfo.map(|r| &s[r]), Some("a@b"));
+        assert_eq!(&s[parts.host], "example.com");
+    }
+
+    #[test]
+    fn accepts_a_valid_ip_literal_host() {
+        let scanner = UrlScanner::default();
+        let s = "see http://[2001:db8::1]:8080/path end";
+        assert_eq!(scanner.scan(s, s.find(':').unwrap()), Some(4..34));
+    }
+
+    #[test]
+    fn accepts_an_ip_literal_zone_id() {
+        let scanner = UrlScanner::default();
+        let s = "see http://[2001:db8::1%eth0]/path end";
+        assert_eq!(scanner.scan(s, s.find(':').unwrap()), Some(4..34));
+    }
+
+    #[test]
+    fn rejects_a_malformed_ip_literal_host() {
+        let scanner = UrlScanner::default();
+        let s = "see http://[notanip]/path end";
+        assert_eq!(scanner.scan(s, s.find(':').unwrap()), None);
+    }
+
+    #[test]
+    fn matches_an_empty_host_absolute_path_url() {
+        // Regression test: the first `/` right after `://` must still be able to end the
+        // match when there's no IP-literal host to its left.
+        let scanner = UrlScanner::default();
+        let s = "see http:/// end";
+        assert_eq!(scanner.scan(s, s.find(':').unwrap()), Some(4..12));
+        let s2 = "see file:/// end";
+        assert_eq!(scanner.scan(s2, s2.find(':').unwrap()), Some(4..12));
+    }
+
+    #[test]
+    fn percent_encoding_validation_accepts_a_full_escape() {
+        let scanner = UrlScanner {
+            validate_percent_encoding: true,
+            ..Default::default()
+        };
+        let s = "see http://example.com/a%20b end";
+        assert_eq!(scanner.scan(s, s.find(':').unwrap()), Some(4..28));
+    }
+
+    #[test]
+    fn percent_encoding_validation_stops_before_a_truncated_escape() {
+        let scanner = UrlScanner {
+            validate_percent_encoding: true,
+            ..Default::default()
+        };
+        let s = "see http://example.com/a%2 end";
+        assert_eq!(scanner.scan(s, s.find(':').unwrap()), Some(4..24));
+    }
+
+    #[test]
+    fn percent_encoding_validation_stops_before_a_non_hex_escape() {
+        let scanner = UrlScanner {
+            validate_percent_encoding: true,
+            ..Default::default()
+        };
+        let s = "see http://example.com/a%zzb end";
+        assert_eq!(scanner.scan(s, s.find(':').unwrap()), Some(4..24));
+    }
+
+    #[test]
+    fn percent_encoding_validation_is_opt_in() {
+        let scanner = UrlScanner::default();
+        let s = "see http://example.com/a%2 end";
+        assert_eq!(scanner.scan(s, s.find(':').unwrap()), Some(4..26));
+    }
+
+    // Feed `s` into a `StreamingScanner` the way a caller growing outward from the trigger
+    // would, stopping each side as soon as it returns `false`.
+    fn run_streaming(scanner: &UrlScanner, s: &str, stop_at: Option<char>) -> Option<Range<usize>> {
+        let trigger_index = s.find(scanner.trigger)?;
+        let offset = if scanner.trigger == '/' { 2 } else { 3 };
+        let after_protocol = trigger_index + offset;
+        let mut streaming = StreamingScanner::new(scanner, after_protocol, stop_at);
+        for c in s[..trigger_index].chars().rev() {
+            if !streaming.advance_left(c) {
+                break;
+            }
+        }
+        for c in s[after_protocol..].chars() {
+            if !streaming.advance_right(c) {
+                break;
+            }
+        }
+        streaming.finish()
+    }
+
+    #[test]
+    fn streaming_matches_batch_scan_for_the_same_input() {
+        let scanner = UrlScanner::default();
+        let s = "see http://example.com/path(nested) end";
+        assert_eq!(
+            run_streaming(&scanner, s, None),
+            scanner.scan(s, s.find(':').unwrap())
+        );
+    }
+
+    #[test]
+    fn streaming_rejects_a_scheme_outside_the_allowlist() {
+        let scanner = UrlScanner {
+            schemes: Some(vec!["http".to_string()]),
+            ..Default::default()
+        };
+        let s = "javascript://alert(1)";
+        assert_eq!(run_streaming(&scanner, s, None), None);
+    }
+
+    #[test]
+    fn streaming_rejects_a_malformed_ip_literal_host() {
+        let scanner = UrlScanner::default();
+        let s = "http://[notanip] end";
+        assert_eq!(run_streaming(&scanner, s, None), None);
+    }
+
+    #[test]
+    fn streaming_result_feeds_into_parts() {
+        let scanner = UrlScanner::default();
+        let s = "see http://example.com/path(nested) end";
+        let range = run_streaming(&scanner, s, None).unwrap();
+        let parts = scanner.parts(s, range).unwrap();
+        assert_eq!(&s[parts.host], "example.com");
+    }
+
+    #[test]
+    fn streaming_matches_batch_scan_in_delimited_mode() {
+        // Regression test: a caller that honors `advance_right`'s documented contract (stop
+        // feeding as soon as it returns `false`) must still see the same result as `scan`,
+        // including the relaxed stop rules inside the delimiter.
+        let scanner = UrlScanner {
+            delimited: true,
+            ..Default::default()
+        };
+        let s = "<http://go.here/to this place> end";
+        assert_eq!(
+            run_streaming(&scanner, s, Some('>')),
+            scanner.scan(s, s.find(':').unwrap())
+        );
+    }
+
+    #[test]
+    fn custom_separator_char_ends_the_url_immediately() {
+        let scanner = UrlScanner {
+            separator_chars: vec!['|'],
+            ..Default::default()
+        };
+        let s = "see http://example.com/a|b end";
+        assert_eq!(scanner.scan(s, s.find(':').unwrap()), Some(4..24));
+    }
+
+    #[test]
+    fn custom_deny_end_char_is_trimmed_from_the_end() {
+        let scanner = UrlScanner {
+            deny_end_chars: vec!['~'],
+            ..Default::default()
+        };
+        let s = "see http://example.com/a~ end";
+        assert_eq!(scanner.scan(s, s.find(':').unwrap()), Some(4..24));
+    }
+
+    #[test]
+    fn separator_and_deny_end_chars_are_empty_by_default() {
+        let scanner = UrlScanner::default();
+        let s = "see http://example.com/a|b end";
+        assert_eq!(scanner.scan(s, s.find(':').unwrap()), Some(4..26));
+        let s2 = "see http://example.com/a~ end";
+        assert_eq!(scanner.scan(s2, s2.find(':').unwrap()), Some(4..25));
+    }
+
+    #[test]
+    fn delimited_mode_matches_up_to_the_closing_angle_bracket() {
+        let scanner = UrlScanner {
+            delimited: true,
+            ..Default::default()
+        };
+        let s = "<http://go.here/to this place> end";
+        assert_eq!(scanner.scan(s, s.find(':').unwrap()), Some(1..29));
+    }
+
+    #[test]
+    fn delimited_mode_handles_nested_parens() {
+        let scanner = UrlScanner {
+            delimited: true,
+            ..Default::default()
+        };
+        let s = "(see http://en.wikipedia.org/wiki/Rust_(programming_language) here)";
+        assert_eq!(scanner.scan(s, s.find(':').unwrap()), Some(5..61));
+    }
+
+    #[test]
+    fn delimited_mode_falls_back_when_the_closing_delimiter_is_never_found() {
+        // Regression test: an opening delimiter with no matching close anywhere in the
+        // remaining text must not relax the stop rules for the rest of the buffer.
+        let scanner = UrlScanner {
+            delimited: true,
+            ..Default::default()
+        };
+        let s = format!("<http://go.here/x {}", "word ".repeat(50));
+        assert_eq!(scanner.scan(&s, s.find(':').unwrap()), Some(1..17));
+    }
 }